@@ -1,3 +1,4 @@
+use std::convert::TryFrom;
 use std::error::Error as StdError;
 use std::fmt;
 use std::str::Chars;
@@ -46,6 +47,15 @@ pub enum Error {
     NumberOverflow,
     /// The value was an empty string (or consists only whitespace)
     Empty,
+    /// A fractional quantity was given for `weeks`, `months` or `years`
+    ///
+    /// Those units are kept as whole calendar fields in
+    /// [`CalendarDuration`], so a fraction of one (e.g. `1.5months`) has
+    /// no sub-day remainder to carry it into; only `days` and smaller
+    /// units accept a fractional quantity.
+    ///
+    /// The field is the byte offset of the start of the number.
+    FractionalCalendarUnit(usize),
 }
 
 impl StdError for Error {}
@@ -72,13 +82,101 @@ impl fmt::Display for Error {
             }
             Error::NumberOverflow => write!(f, "number is too large"),
             Error::Empty => write!(f, "value was empty"),
+            Error::FractionalCalendarUnit(offset) => write!(
+                f,
+                "fractional quantity at {} is only allowed for days and \
+                smaller units, not weeks, months or years",
+                offset
+            ),
         }
     }
 }
 
 /// A wrapper type that allows you to Display a Duration
+///
+/// By default all nonzero units are printed; use [`precision`][
+/// FormattedDuration::precision] to limit the output to the `n`
+/// most-significant nonzero units.
 #[derive(Debug, Clone)]
-pub struct FormattedDuration(Duration);
+pub struct FormattedDuration(Duration, usize);
+
+/// A duration split into calendar fields rather than flattened into seconds
+///
+/// Unlike [`Duration`], which has no notion of a month or year, this keeps
+/// `years`, `months`, `weeks` and `days` as separate integer counts, so a
+/// caller that is adding the result to a real calendar date can step months
+/// and years as calendar units (e.g. "1 month" from Jan 31 to Feb 28/29)
+/// instead of treating them as a fixed number of seconds. `weeks` and `days`
+/// are exact (a week is always `7 * 86400` seconds), they are only kept
+/// separate from `duration` for convenience.
+///
+/// Use [`parse_duration_components`] to parse one, and [`to_duration`][
+/// CalendarDuration::to_duration] to flatten it back into a plain
+/// [`Duration`] using the same 30.44-day/365.25-day approximations as
+/// [`parse_duration`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CalendarDuration {
+    /// Number of years (365.25 days when flattened)
+    pub years: u64,
+    /// Number of months (30.44 days when flattened)
+    pub months: u64,
+    /// Number of weeks (exactly 7 days)
+    pub weeks: u64,
+    /// Number of days (exactly 86400 seconds)
+    pub days: u64,
+    /// The sum of all units smaller than a day, as a plain `Duration`
+    ///
+    /// Hours and smaller units are not carried into `days`, so this can
+    /// exceed 24 hours (e.g. `25h` leaves `days` at `0`).
+    ///
+    /// A fractional `days` quantity (e.g. `1.5days`) is allowed and folds
+    /// its sub-day remainder in here; `weeks`, `months` and `years` have no
+    /// sub-day remainder to carry, so a fractional quantity on those is
+    /// rejected with `Error::FractionalCalendarUnit` instead of being
+    /// folded in here.
+    pub duration: Duration,
+}
+
+impl CalendarDuration {
+    /// Flattens the calendar fields into a single `Duration`
+    ///
+    /// `months` and `years` are approximated as 30.44 and 365.25 days
+    /// respectively, matching the behavior of [`parse_duration`]. Callers
+    /// that have a real calendar date to add to should instead apply
+    /// `years`, `months`, `weeks` and `days` directly and only use this
+    /// for the flattened, approximate case.
+    pub fn to_duration(&self) -> Result<Duration, Error> {
+        let mut sec = self.days.mul(86400)?;
+        sec = sec.add(self.weeks.mul(86400 * 7)?)?;
+        sec = sec.add(self.months.mul(2_630_016)?)?; // 30.44d
+        sec = sec.add(self.years.mul(31_557_600)?)?; // 365.25d
+        sec = sec.add(self.duration.as_secs())?;
+        Ok(Duration::new(sec, self.duration.subsec_nanos()))
+    }
+}
+
+/// A [`Duration`] paired with a sign, able to represent negative spans
+///
+/// `std::time::Duration` is always non-negative, so "time remaining" or
+/// "time overdue" calculations that can legitimately go negative need a
+/// wrapper like this one. Use [`parse_signed_duration`] to parse one and
+/// [`format_signed_duration`] to render it back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SignedDuration {
+    negative: bool,
+    duration: Duration,
+}
+
+impl SignedDuration {
+    /// Returns `true` if this duration is negative
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+    /// Returns the magnitude of this duration, discarding the sign
+    pub fn abs(&self) -> Duration {
+        self.duration
+    }
+}
 
 trait OverflowOp: Sized {
     fn mul(self, other: Self) -> Result<Self, Error>;
@@ -98,6 +196,10 @@ struct Parser<'a> {
     iter: Chars<'a>,
     src: &'a str,
     current: (u64, u64),
+    years: u64,
+    months: u64,
+    weeks: u64,
+    days: u64,
 }
 
 impl<'a> Parser<'a> {
@@ -105,6 +207,29 @@ impl<'a> Parser<'a> {
         self.src.len() - self.iter.as_str().len()
     }
 
+    /// Consumes a leading `-` or `+` sign, if present, and reports whether
+    /// it was negative
+    ///
+    /// Only the very first character of the input is considered a sign;
+    /// a `-`/`+` anywhere else is rejected by the regular parser: with
+    /// `InvalidCharacter` inside or right after a number or unit (`5-3m`,
+    /// `5m-3s`), and with `NumberExpected` where a number should begin
+    /// (`5m -3s`, `--5m`).
+    fn parse_sign(&mut self) -> bool {
+        let mut iter = self.iter.clone();
+        match iter.next() {
+            Some('-') => {
+                self.iter = iter;
+                true
+            }
+            Some('+') => {
+                self.iter = iter;
+                false
+            }
+            _ => false,
+        }
+    }
+
     fn parse_first_char(&mut self) -> Result<Option<u64>, Error> {
         let off = self.off();
         for c in self.iter.by_ref() {
@@ -120,21 +245,46 @@ impl<'a> Parser<'a> {
         }
         Ok(None)
     }
-    fn parse_unit(&mut self, n: u64, start: usize, end: usize)
+    /// Applies a scanned `n` (plus an optional `frac / frac_scale` fraction)
+    /// of the unit named by `src[start..end]`
+    ///
+    /// `frac_scale` is `0` when the number had no decimal point at all, and
+    /// `10^(number of fractional digits)` otherwise. `num_start` is the
+    /// byte offset of the start of the number, used for error reporting.
+    fn parse_unit(&mut self, n: u64, frac: u64, frac_scale: u64,
+        num_start: usize, start: usize, end: usize)
         -> Result<(), Error>
     {
-        let (mut sec, nsec) = match &self.src[start..end] {
-            "nanos" | "nsec" | "ns" => (0u64, n),
-            "usec" | "µs" => (0u64, n.mul(1000)?),
-            "millis" | "msec" | "ms" => (0u64, n.mul(1_000_000)?),
-            "seconds" | "second" | "secs" | "sec" | "s" => (n, 0),
-            "minutes" | "minute" | "min" | "mins" | "m"
-            => (n.mul(60)?, 0),
-            "hours" | "hour" | "hr" | "hrs" | "h" => (n.mul(3600)?, 0),
-            "days" | "day" | "d" => (n.mul(86400)?, 0),
-            "weeks" | "week" | "w" => (n.mul(86400*7)?, 0),
-            "months" | "month" | "M" => (n.mul(2_630_016)?, 0), // 30.44d
-            "years" | "year" | "y" => (n.mul(31_557_600)?, 0), // 365.25d
+        // `routed` units are accounted for directly in a `CalendarDuration`
+        // field above; the other units fold `n` into the sub-day duration
+        // instead. Only `days` is both routed and `fraction_allowed`: its
+        // whole part is routed, but its fractional remainder is genuinely
+        // sub-day, so it is allowed to fall through to the `duration` field
+        // below. A fraction on `weeks`, `months` or `years` is rejected
+        // instead of being silently flattened.
+        let (ns_per_unit, routed, fraction_allowed): (u64, bool, bool) = match &self.src[start..end] {
+            "nanos" | "nsec" | "ns" => (1, false, true),
+            "usec" | "µs" => (1_000, false, true),
+            "millis" | "msec" | "ms" => (1_000_000, false, true),
+            "seconds" | "second" | "secs" | "sec" | "s" => (1_000_000_000, false, true),
+            "minutes" | "minute" | "min" | "mins" | "m" => (60_000_000_000, false, true),
+            "hours" | "hour" | "hr" | "hrs" | "h" => (3_600_000_000_000, false, true),
+            "days" | "day" | "d" => {
+                self.days = self.days.add(n)?;
+                (86_400_000_000_000, true, true)
+            }
+            "weeks" | "week" | "w" => {
+                self.weeks = self.weeks.add(n)?;
+                (604_800_000_000_000, true, false)
+            }
+            "months" | "month" | "M" => { // 30.44d
+                self.months = self.months.add(n)?;
+                (2_630_016_000_000_000, true, false)
+            }
+            "years" | "year" | "y" => { // 365.25d
+                self.years = self.years.add(n)?;
+                (31_557_600_000_000_000, true, false)
+            }
             _ => {
                 return Err(Error::UnknownUnit {
                     start, end,
@@ -143,6 +293,33 @@ impl<'a> Parser<'a> {
                 });
             }
         };
+        if frac_scale > 0 && !fraction_allowed {
+            return Err(Error::FractionalCalendarUnit(num_start));
+        }
+        // The whole part of calendar units (days/weeks/months/years) is
+        // already accounted for above; only a fractional remainder of them
+        // is folded into the sub-day duration.
+        let whole_ns: u128 = if routed {
+            0
+        } else {
+            (n as u128).checked_mul(ns_per_unit as u128).ok_or(Error::NumberOverflow)?
+        };
+        let frac_ns: u128 = if frac_scale > 0 {
+            let scale = frac_scale as u128;
+            (frac as u128 * ns_per_unit as u128 + scale / 2) / scale
+        } else {
+            0
+        };
+        let total_ns = whole_ns.checked_add(frac_ns).ok_or(Error::NumberOverflow)?;
+        let add_sec = u64::try_from(total_ns / 1_000_000_000)
+            .map_err(|_| Error::NumberOverflow)?;
+        let add_nsec = (total_ns % 1_000_000_000) as u64;
+        self.add_subday(add_sec, add_nsec)
+    }
+
+    /// Adds a sub-day `(seconds, nanoseconds)` pair into the running total
+    fn add_subday(&mut self, sec: u64, nsec: u64) -> Result<(), Error> {
+        let mut sec = sec;
         let mut nsec = self.current.1.add(nsec)?;
         if nsec > 1_000_000_000 {
             sec = sec.add(nsec / 1_000_000_000)?;
@@ -153,19 +330,44 @@ impl<'a> Parser<'a> {
         Ok(())
     }
 
-    fn parse(mut self) -> Result<Duration, Error> {
+    fn parse(mut self) -> Result<CalendarDuration, Error> {
         let mut n = self.parse_first_char()?.ok_or(Error::Empty)?;
         'outer: loop {
             let mut off = self.off();
+            // The first digit (always one byte) was already consumed
+            let num_start = off - 1;
+            // A single decimal point is allowed per number; `frac_scale`
+            // stays `0` until a dot is seen, and becomes `10^digits` once
+            // at least one fractional digit has been scanned.
+            let mut dot_off = off;
+            let mut frac = 0u64;
+            let mut frac_scale = 0u64;
             while let Some(c) = self.iter.next() {
                 match c {
+                    '0'..='9' if frac_scale > 0 => {
+                        frac = frac.checked_mul(10)
+                            .and_then(|x| x.checked_add(c as u64 - '0' as u64))
+                            .ok_or(Error::NumberOverflow)?;
+                        frac_scale = frac_scale.checked_mul(10)
+                            .ok_or(Error::NumberOverflow)?;
+                    }
                     '0'..='9' => {
                         n = n.checked_mul(10)
                             .and_then(|x| x.checked_add(c as u64 - '0' as u64))
                             .ok_or(Error::NumberOverflow)?;
                     }
+                    '.' if frac_scale == 0 => {
+                        dot_off = off;
+                        frac_scale = 1;
+                    }
+                    '.' => {
+                        return Err(Error::NumberExpected(off));
+                    }
                     c if c.is_whitespace() => {}
                     'a'..='z' | 'A'..='Z' => {
+                        if frac_scale == 1 {
+                            return Err(Error::NumberExpected(dot_off));
+                        }
                         break;
                     }
                     _ => {
@@ -174,12 +376,15 @@ impl<'a> Parser<'a> {
                 }
                 off = self.off();
             }
+            if frac_scale == 1 {
+                return Err(Error::NumberExpected(dot_off));
+            }
             let start = off;
             let mut off = self.off();
             while let Some(c) = self.iter.next() {
                 match c {
                     '0'..='9' => {
-                        self.parse_unit(n, start, off)?;
+                        self.parse_unit(n, frac, frac_scale, num_start, start, off)?;
                         n = c as u64 - '0' as u64;
                         continue 'outer;
                     }
@@ -191,11 +396,16 @@ impl<'a> Parser<'a> {
                 }
                 off = self.off();
             }
-            self.parse_unit(n, start, off)?;
+            self.parse_unit(n, frac, frac_scale, num_start, start, off)?;
             n = match self.parse_first_char()? {
                 Some(n) => n,
-                None => return Ok(
-                    Duration::new(self.current.0, self.current.1 as u32)),
+                None => return Ok(CalendarDuration {
+                    years: self.years,
+                    months: self.months,
+                    weeks: self.weeks,
+                    days: self.days,
+                    duration: Duration::new(self.current.0, self.current.1 as u32),
+                }),
             };
         }
     }
@@ -218,6 +428,10 @@ impl<'a> Parser<'a> {
 /// * `months`, `month`, `M` -- defined as 30.44 days
 /// * `years`, `year`, `y` -- defined as 365.25 days
 ///
+/// Each number may have a single fractional part, e.g. `1.5h` or `0.25s`;
+/// a fractional quantity of `weeks`, `months` or `years` is rejected with
+/// `Error::FractionalCalendarUnit`.
+///
 /// # Examples
 ///
 /// ```
@@ -226,15 +440,79 @@ impl<'a> Parser<'a> {
 ///
 /// assert_eq!(parse_duration("2h 37min"), Ok(Duration::new(9420, 0)));
 /// assert_eq!(parse_duration("32ms"), Ok(Duration::new(0, 32_000_000)));
+/// assert_eq!(parse_duration("1.5h"), Ok(Duration::new(5400, 0)));
 /// ```
 pub fn parse_duration(s: &str) -> Result<Duration, Error> {
+    parse_duration_components(s)?.to_duration()
+}
+
+/// Parse duration object keeping months/years/weeks/days as separate fields
+///
+/// This accepts the same syntax as [`parse_duration`], but instead of
+/// flattening `months` and `years` into a fixed number of seconds, it
+/// returns a [`CalendarDuration`] that keeps them (along with `weeks` and
+/// `days`) as separate integer fields. This is useful when the result is
+/// going to be applied to a real calendar date, where "1 month" means a
+/// calendar step rather than exactly `2_630_016` seconds.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use humantime::{parse_duration_components, CalendarDuration};
+///
+/// assert_eq!(parse_duration_components("1month 2days"), Ok(CalendarDuration {
+///     months: 1,
+///     days: 2,
+///     ..Default::default()
+/// }));
+/// ```
+pub fn parse_duration_components(s: &str) -> Result<CalendarDuration, Error> {
     Parser {
         iter: s.chars(),
         src: s,
         current: (0, 0),
+        years: 0,
+        months: 0,
+        weeks: 0,
+        days: 0,
     }.parse()
 }
 
+/// Parse an optionally-signed duration like `-5m` or `+2h 30min`
+///
+/// This accepts the same syntax as [`parse_duration`], plus an optional
+/// leading `-` or `+`. The sign is only recognized at the very start of the
+/// string; a sign anywhere else is rejected as it would be by
+/// [`parse_duration`]: with `InvalidCharacter` inside or right after a
+/// number or unit (`5-3m`), and with `NumberExpected` where a number should
+/// begin (`5m -3s`).
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use humantime::parse_signed_duration;
+///
+/// let d = parse_signed_duration("-5m").unwrap();
+/// assert!(d.is_negative());
+/// assert_eq!(d.abs(), Duration::new(300, 0));
+/// ```
+pub fn parse_signed_duration(s: &str) -> Result<SignedDuration, Error> {
+    let mut parser = Parser {
+        iter: s.chars(),
+        src: s,
+        current: (0, 0),
+        years: 0,
+        months: 0,
+        weeks: 0,
+        days: 0,
+    };
+    let negative = parser.parse_sign();
+    let duration = parser.parse()?.to_duration()?;
+    Ok(SignedDuration { negative, duration })
+}
+
 /// Formats duration into a human-readable string
 ///
 /// Note: this format is guaranteed to have same value when using
@@ -251,9 +529,44 @@ pub fn parse_duration(s: &str) -> Result<Duration, Error> {
 /// assert_eq!(format_duration(val1).to_string(), "2h 37m");
 /// let val2 = Duration::new(0, 32_000_000);
 /// assert_eq!(format_duration(val2).to_string(), "32ms");
+///
+/// // limit to the 1 most-significant unit, rounding the rest into it
+/// let val3 = Duration::new(7170, 0); // 1h59m30s
+/// assert_eq!(format_duration(val3).precision(1).to_string(), "2h");
 /// ```
 pub fn format_duration(val: Duration) -> FormattedDuration {
-    FormattedDuration(val)
+    FormattedDuration(val, usize::MAX)
+}
+
+/// Formats a signed duration into a human-readable string
+///
+/// This is the sibling of [`format_duration`] for [`SignedDuration`]: it
+/// renders a leading `-` for negative values so the result round-trips
+/// through [`parse_signed_duration`].
+///
+/// # Examples
+///
+/// ```
+/// use humantime::{parse_signed_duration, format_signed_duration};
+///
+/// let d = parse_signed_duration("-5m").unwrap();
+/// assert_eq!(format_signed_duration(d).to_string(), "-5m");
+/// ```
+pub fn format_signed_duration(val: SignedDuration) -> FormattedSignedDuration {
+    FormattedSignedDuration(val)
+}
+
+/// A wrapper type that allows you to Display a SignedDuration
+#[derive(Debug, Clone)]
+pub struct FormattedSignedDuration(SignedDuration);
+
+impl fmt::Display for FormattedSignedDuration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0.negative {
+            f.write_str("-")?;
+        }
+        fmt::Display::fmt(&FormattedDuration(self.0.duration, usize::MAX), f)
+    }
 }
 
 fn item_plural(f: &mut fmt::Formatter, started: &mut bool,
@@ -285,47 +598,106 @@ fn item(f: &mut fmt::Formatter, started: &mut bool, name: &str, value: u32)
     Ok(())
 }
 
+/// Size, in nanoseconds, of each unit `decompose_duration` breaks a
+/// duration into, largest first
+const UNIT_NANOS: [u128; 9] = [
+    31_557_600_000_000_000, // year, 365.25d
+    2_630_016_000_000_000,  // month, 30.44d
+    86_400_000_000_000,     // day
+    3_600_000_000_000,      // hour
+    60_000_000_000,         // minute
+    1_000_000_000,          // second
+    1_000_000,              // millisecond
+    1_000,                  // microsecond
+    1,                      // nanosecond
+];
+
+/// Breaks a duration, expressed as total nanoseconds, into the same
+/// year/month/day/h/m/s/ms/µs/ns fields that `Display` prints
+fn decompose_duration(total_nanos: u128) -> [u64; 9] {
+    let secs = (total_nanos / 1_000_000_000) as u64;
+    let nanos = (total_nanos % 1_000_000_000) as u32;
+
+    let years = secs / 31_557_600; // 365.25d
+    let ydays = secs % 31_557_600;
+    let months = ydays / 2_630_016; // 30.44d
+    let mdays = ydays % 2_630_016;
+    let days = mdays / 86400;
+    let day_secs = mdays % 86400;
+    let hours = day_secs / 3600;
+    let minutes = day_secs % 3600 / 60;
+    let seconds = day_secs % 60;
+
+    let millis = (nanos / 1_000_000) as u64;
+    let micros = (nanos / 1000 % 1000) as u64;
+    let nanosec = (nanos % 1000) as u64;
+
+    [years, months, days, hours, minutes, seconds, millis, micros, nanosec]
+}
+
 impl FormattedDuration {
     /// Returns a reference to the [`Duration`][] that is being formatted.
     pub fn get_ref(&self) -> &Duration {
         &self.0
     }
+
+    /// Limits formatting to the `n` most-significant nonzero units
+    ///
+    /// Units beyond the `n`-th are not dropped silently: they are rounded
+    /// into the last unit that is printed, so `1h59m30s` at precision `1`
+    /// prints as `2h` rather than `1h`. `n` is clamped to a minimum of `1`,
+    /// since a duration always has at least one unit to show.
+    pub fn precision(mut self, n: usize) -> Self {
+        self.1 = n.max(1);
+        self
+    }
 }
 
 impl fmt::Display for FormattedDuration {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let secs = self.0.as_secs();
-        let nanos = self.0.subsec_nanos();
+        let total_nanos = self.0.as_secs() as u128 * 1_000_000_000
+            + self.0.subsec_nanos() as u128;
 
-        if secs == 0 && nanos == 0 {
+        if total_nanos == 0 {
             f.write_str("0s")?;
             return Ok(());
         }
 
-        let years = secs / 31_557_600;  // 365.25d
-        let ydays = secs % 31_557_600;
-        let months = ydays / 2_630_016;  // 30.44d
-        let mdays = ydays % 2_630_016;
-        let days = mdays / 86400;
-        let day_secs = mdays % 86400;
-        let hours = day_secs / 3600;
-        let minutes = day_secs % 3600 / 60;
-        let seconds = day_secs % 60;
+        let mut values = decompose_duration(total_nanos);
+        let nonzero = values.iter().filter(|&&v| v > 0).count();
+        if self.1 < nonzero {
+            let round_unit_nanos = values.iter()
+                .zip(UNIT_NANOS.iter())
+                .filter(|&(&v, _)| v > 0)
+                .nth(self.1.saturating_sub(1))
+                .map(|(_, &size)| size)
+                .unwrap_or(1);
+            let rounded = (total_nanos + round_unit_nanos / 2)
+                / round_unit_nanos * round_unit_nanos;
+            values = decompose_duration(rounded);
+        }
 
-        let millis = nanos / 1_000_000;
-        let micros = nanos / 1000 % 1000;
-        let nanosec = nanos % 1000;
+        const NAMES: [&str; 9] =
+            ["year", "month", "day", "h", "m", "s", "ms", "µs", "ns"];
+        const PLURAL: [bool; 9] =
+            [true, true, true, false, false, false, false, false, false];
 
         let ref mut started = false;
-        item_plural(f, started, "year", years)?;
-        item_plural(f, started, "month", months)?;
-        item_plural(f, started, "day", days)?;
-        item(f, started, "h", hours as u32)?;
-        item(f, started, "m", minutes as u32)?;
-        item(f, started, "s", seconds as u32)?;
-        item(f, started, "ms", millis)?;
-        item(f, started, "µs", micros)?;
-        item(f, started, "ns", nanosec)?;
+        let mut printed = 0;
+        for i in 0..9 {
+            if values[i] == 0 {
+                continue;
+            }
+            if printed >= self.1 {
+                break;
+            }
+            if PLURAL[i] {
+                item_plural(f, started, NAMES[i], values[i])?;
+            } else {
+                item(f, started, NAMES[i], values[i] as u32)?;
+            }
+            printed += 1;
+        }
         Ok(())
     }
 }